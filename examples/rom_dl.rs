@@ -1,118 +1,157 @@
-use myrient_filter::{FilterOptions, Rom, RomLister};
+use myrient_filter::sync_state::{self, SyncEntry, SyncState};
+use myrient_filter::{config, datfile, extract, group_discs, write_playlist};
+use myrient_filter::{DatFile, Downloader, Rom, RomLister};
 use std::path::{Path, PathBuf};
-
-async fn download_file(
-    url: &str,
-    dest: PathBuf,
-) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
-    let client = reqwest::Client::new();
-    let response = client.get(url).send().await?;
-
-    println!("url: {}", url);
-
-    // Check if response is text based on content-type header
-    let content_type = response
-        .headers()
-        .get(reqwest::header::CONTENT_TYPE)
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("");
-
-    if content_type.contains("text") {
-        let text = response.text().await?;
-        println!("Received text response:\n{}", text);
-        Ok(None)
-    } else {
-        let bytes = response.bytes().await?;
-        tokio::fs::write(&dest, &bytes).await?;
-        Ok(Some(dest))
-    }
-}
-
-async fn download_rom(rom: &Rom, download_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+use std::sync::Arc;
+
+async fn download_rom(
+    downloader: &Downloader,
+    rom: &Rom,
+    download_path: &Path,
+    block_extract: bool,
+    dat: Option<&DatFile>,
+) -> Result<Option<(Vec<PathBuf>, Option<String>)>, Box<dyn std::error::Error>> {
     let dest = download_path.join(&rom.filename);
     println!("Downloading {} to {:?}", rom.filename, dest);
 
-    if let Some(dest) = download_file(&rom.url, dest).await? {
-        if dest.extension().unwrap_or_default() == "zip" {
-            let dest_dir = dest.with_extension("");
-            let status = std::process::Command::new("unzip")
-                .arg("-o")
-                .arg(&dest)
-                .arg("-d")
-                .arg(&dest_dir)
-                .status()?;
-
-            if !status.success() {
-                eprintln!("Failed to unzip {:?}", dest);
-                return Ok(());
-            }
+    let Some(dest) = downloader.download(&rom.url, &dest).await? else {
+        eprintln!("Skipping {} - received text response", rom.filename);
+        return Ok(None);
+    };
 
+    let extracted = if !block_extract {
+        let extracted = extract::extract(&dest, download_path)?;
+        if extracted != [dest.clone()] {
             tokio::fs::remove_file(&dest).await?;
-            let filename_stripped = dest.file_stem().unwrap().to_str().unwrap();
-            let resulting_folder = download_path.join(filename_stripped);
-            if resulting_folder.exists() {
-                println!("Moving files from {:?} to {:?}", dest_dir, resulting_folder);
-                let mut entries = tokio::fs::read_dir(&resulting_folder).await?;
-                while let Some(entry) = entries.next_entry().await? {
-                    let entry_path = entry.path();
-                    let entry_filename = entry_path.file_name().unwrap().to_str().unwrap();
-                    let new_path = download_path.join(entry_filename);
-                    tokio::fs::copy(&entry_path, &new_path).await?;
-                }
-                tokio::fs::remove_dir_all(&dest_dir).await?;
-            }
-
-            // println!("Resulting folder: {:?}", resulting_folder);
         }
+        extracted
     } else {
-        eprintln!("Skipping {} - received text response", rom.filename);
+        vec![dest]
+    };
+
+    // Track the CRC32 of the first file verified against the DAT as the
+    // ROM's recorded hash - most archives extract to a single primary file.
+    let mut verified_hash = None;
+    if let Some(dat) = dat {
+        for file in &extracted {
+            let name = file.file_name().unwrap().to_string_lossy();
+            let hashes = datfile::verify(dat, &name, file)?;
+            verified_hash.get_or_insert(hashes.crc32);
+        }
     }
 
-    Ok(())
+    Ok(Some((extracted, verified_hash)))
 }
 
-// Download all SNES retail ROMs released in the USA
-// excluding prototypes, betas, and other non-retail ROMs,
-// and only get the latest release of each title (if multiple revisions exist)
-// and save them to the "snes" directory
+// Download ROMs for whichever profile is selected (or the config's default
+// profile if none is given), following filters, target system, and download
+// directory defined in config.toml rather than a hard-coded FilterOptions.
+// Already-downloaded ROMs are skipped on repeat runs; pass `--prune` to also
+// delete local files no longer matched by the current filter.
+//
+// Usage: rom_dl [profile-name] [--prune]
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let options = FilterOptions {
-        region_limit: true,
-        region: "USA".to_string(),
-        smart_filters: true,
-        exclude_patterns: vec![
-            "Pirate".to_string(),
-            "Beta".to_string(),
-            "Proto".to_string(),
-            "Enhancement Chip".to_string(),
-            "Tech Demo".to_string(),
-            "Competition Cart, Nintendo Power mail-order".to_string(),
-            "Sample".to_string(),
-            "Aftermarket".to_string(),
-            "Demo".to_string(),
-            "Unl".to_string(),
-        ],
-        latest_revision: true,
-    };
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let prune = args.iter().any(|arg| arg == "--prune");
+    let profile_name = args.into_iter().find(|arg| arg != "--prune");
+
+    let config_path = config::default_config_path()
+        .ok_or("could not determine config directory for this platform")?;
 
-    let lister = RomLister::new(options);
+    let (lister, profile) = RomLister::from_profile(&config_path, profile_name.as_deref())?;
 
     // List base directories
     println!("Available base directories:");
-    for dir in lister.list_directories(Some("No-Intro")).await? {
+    for dir in lister.list_directories(Some(&profile.subdir)).await? {
         println!("- {}", dir);
     }
 
-    const SNES: &str = "Nintendo - Super Nintendo Entertainment System";
-    const DOWNLOAD_PATH: &str = "snes/";
-    let download_path = PathBuf::from(DOWNLOAD_PATH);
+    let download_path = PathBuf::from(&profile.download_dir);
+    tokio::fs::create_dir_all(&download_path).await?;
+    let dat = profile
+        .filter
+        .dat_path
+        .as_deref()
+        .map(DatFile::load)
+        .transpose()?;
+
+    let state_path = SyncState::path_for(&download_path);
+    let mut state = SyncState::load(&state_path)?;
+
+    println!("Listing ROMs for {}:", profile.target_system);
+    let roms = lister
+        .list_roms(&profile.target_system, &profile.subdir)
+        .await?;
+    let plan = sync_state::plan_sync(&roms, &state, prune);
+
+    println!(
+        "{} ROM(s) to download, {} unchanged, {} to prune",
+        plan.to_download.len(),
+        roms.len() - plan.to_download.len(),
+        plan.to_prune.len()
+    );
+
+    let downloader = Downloader::new(profile.filter.max_concurrent_downloads);
+    let dat = Arc::new(dat);
+    let downloads = plan.to_download.iter().map(|rom| {
+        let rom = (*rom).clone();
+        let downloader = downloader.clone();
+        let download_path = download_path.clone();
+        let dat = Arc::clone(&dat);
+        async move {
+            let result = download_rom(
+                &downloader,
+                &rom,
+                &download_path,
+                profile.filter.block_extract,
+                dat.as_ref().as_ref(),
+            )
+            .await;
+            (rom, result)
+        }
+    });
+
+    for (rom, result) in futures_util::future::join_all(downloads).await {
+        match result {
+            Ok(Some((files, verified_hash))) => {
+                let size = files
+                    .iter()
+                    .filter_map(|path| std::fs::metadata(path).ok())
+                    .map(|meta| meta.len())
+                    .sum();
+                state.record(
+                    rom.filename.clone(),
+                    SyncEntry {
+                        source_url: rom.url.clone(),
+                        files,
+                        size,
+                        verified_hash,
+                    },
+                );
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to download {}: {}", rom.filename, e),
+        }
+    }
+
+    for filename in &plan.to_prune {
+        if let Some(entry) = state.entries.get(filename) {
+            for file in &entry.files {
+                let _ = std::fs::remove_file(file);
+            }
+        }
+        state.entries.remove(filename);
+        println!("Pruned {}", filename);
+    }
+
+    state.save(&state_path)?;
 
-    println!("Listing ROMs for {}:", SNES);
-    for rom in lister.list_roms(SNES, "No-Intro").await? {
-        if let Err(e) = download_rom(&rom, &download_path).await {
-            eprintln!("Failed to download {}: {}", rom.filename, e);
+    if profile.filter.generate_playlists {
+        for (game_name, discs) in group_discs(&roms) {
+            let path = write_playlist(&download_path, &game_name, &discs)?;
+            println!("Wrote playlist {:?}", path);
         }
     }
 
@@ -0,0 +1,94 @@
+use crate::FilterOptions;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A named set of filter options plus the system/destination info needed to
+/// actually run a sync, e.g. `[profiles.snes-usa]` in `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(flatten)]
+    pub filter: FilterOptions,
+    /// The Myrient system directory name, e.g. "Nintendo - Super Nintendo Entertainment System"
+    pub target_system: String,
+    /// The top-level subdir to look in, e.g. "No-Intro"
+    pub subdir: String,
+    pub download_dir: PathBuf,
+}
+
+/// Top-level shape of `config.toml`: an optional unnamed default profile plus
+/// any number of named profiles under `[profiles.<name>]`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub default: Option<Profile>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Config {
+    /// Load and parse a config file from `path`. Both `.toml` and `.json` are
+    /// supported, selected by file extension.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = std::fs::read_to_string(path)?;
+
+        let config = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&contents)?,
+            _ => toml::from_str(&contents)?,
+        };
+
+        Ok(config)
+    }
+
+    /// Resolve a profile by name, falling back to `default` when `name` is `None`.
+    pub fn profile(&self, name: Option<&str>) -> Option<&Profile> {
+        match name {
+            Some(name) => self.profiles.get(name),
+            None => self.default.as_ref(),
+        }
+    }
+}
+
+/// Standard config file location: `$XDG_CONFIG_HOME/myrient-filter/config.toml`
+/// (or the platform equivalent via the `dirs` crate).
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("myrient-filter").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profiles() {
+        let toml_str = r#"
+            [default]
+            region_limit = true
+            region_priority = ["USA", "World"]
+            smart_filters = true
+            exclude_patterns = ["Beta"]
+            latest_revision = true
+            target_system = "Nintendo - Super Nintendo Entertainment System"
+            subdir = "No-Intro"
+            download_dir = "snes/"
+
+            [profiles.psx-europe]
+            region_limit = true
+            region_priority = ["Europe", "World"]
+            smart_filters = true
+            exclude_patterns = []
+            latest_revision = true
+            target_system = "Sony - PlayStation"
+            subdir = "No-Intro"
+            download_dir = "psx/"
+        "#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert!(config.default.is_some());
+        assert_eq!(
+            config.profile(Some("psx-europe")).unwrap().target_system,
+            "Sony - PlayStation"
+        );
+        assert!(config.profile(Some("missing")).is_none());
+    }
+}
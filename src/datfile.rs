@@ -0,0 +1,248 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A single `<rom>` entry from a Logiqx DAT, e.g.
+/// `<rom name="Super Game (USA).sfc" size="1048576" crc="deadbeef" md5="..." sha1="..."/>`
+#[derive(Debug, Clone, Deserialize)]
+pub struct DatRom {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@size")]
+    pub size: u64,
+    #[serde(rename = "@crc")]
+    pub crc: String,
+    #[serde(rename = "@md5", default)]
+    pub md5: Option<String>,
+    #[serde(rename = "@sha1", default)]
+    pub sha1: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct DatGame {
+    #[serde(rename = "rom", default)]
+    roms: Vec<DatRom>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Datafile {
+    #[serde(rename = "game", default)]
+    games: Vec<DatGame>,
+}
+
+/// A parsed No-Intro DAT, keyed by ROM filename stem for O(1) lookup during
+/// filtering and post-download verification.
+#[derive(Debug, Clone, Default)]
+pub struct DatFile {
+    roms_by_name: HashMap<String, DatRom>,
+}
+
+/// Key a ROM filename by its stem (filename without extension), so a DAT's
+/// `<rom name="Foo.sfc">` still matches the Myrient listing's `Foo.zip`
+/// wrapper - No-Intro DATs record the decompressed name, but the file we're
+/// actually checking against is almost always still zipped.
+fn stem_key(filename: &str) -> String {
+    Path::new(filename)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| filename.to_string())
+}
+
+impl DatFile {
+    pub fn parse(xml: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let parsed: Datafile = quick_xml::de::from_str(xml)?;
+
+        let mut roms_by_name = HashMap::new();
+        for game in parsed.games {
+            for rom in game.roms {
+                roms_by_name.insert(stem_key(&rom.name), rom);
+            }
+        }
+
+        Ok(Self { roms_by_name })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let xml = std::fs::read_to_string(path)?;
+        Self::parse(&xml)
+    }
+
+    pub fn get(&self, filename: &str) -> Option<&DatRom> {
+        self.roms_by_name.get(&stem_key(filename))
+    }
+
+    pub fn contains(&self, filename: &str) -> bool {
+        self.roms_by_name.contains_key(&stem_key(filename))
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms_by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms_by_name.is_empty()
+    }
+}
+
+/// Computed hashes for a downloaded file, used to check against a `DatRom`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHashes {
+    pub size: u64,
+    pub crc32: String,
+    pub md5: Option<String>,
+    pub sha1: Option<String>,
+}
+
+impl FileHashes {
+    /// Hash `path`, computing MD5/SHA1 only when the DAT entry carries them
+    /// so a CRC-only DAT doesn't pay for hashes it will never check. Reads
+    /// the file in fixed-size chunks rather than loading it whole, since
+    /// No-Intro dumps for disc-based systems can run into the gigabytes.
+    pub fn compute(path: &Path, want: &DatRom) -> Result<Self, Box<dyn std::error::Error>> {
+        use md5::Md5;
+        use sha1::{Digest, Sha1};
+        use std::io::Read;
+
+        let mut file = std::fs::File::open(path)?;
+        let mut buf = [0u8; 64 * 1024];
+
+        let mut crc_hasher = crc32fast::Hasher::new();
+        let mut md5_hasher = want.md5.is_some().then(Md5::new);
+        let mut sha1_hasher = want.sha1.is_some().then(Sha1::new);
+        let mut size = 0u64;
+
+        loop {
+            let read = file.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            let chunk = &buf[..read];
+            size += read as u64;
+            crc_hasher.update(chunk);
+            if let Some(hasher) = md5_hasher.as_mut() {
+                hasher.update(chunk);
+            }
+            if let Some(hasher) = sha1_hasher.as_mut() {
+                hasher.update(chunk);
+            }
+        }
+
+        let crc32 = format!("{:08x}", crc_hasher.finalize());
+        let md5 = md5_hasher.map(|hasher| format!("{:x}", hasher.finalize()));
+        let sha1 = sha1_hasher.map(|hasher| format!("{:x}", hasher.finalize()));
+
+        Ok(Self {
+            size,
+            crc32,
+            md5,
+            sha1,
+        })
+    }
+}
+
+/// Error returned when a downloaded file doesn't match its DAT entry.
+#[derive(Debug)]
+pub struct VerifyMismatch {
+    pub filename: String,
+    pub expected: DatRom,
+    pub actual: FileHashes,
+}
+
+impl std::fmt::Display for VerifyMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} failed verification: expected crc={} size={}, got crc={} size={}",
+            self.filename, self.expected.crc, self.expected.size, self.actual.crc32, self.actual.size
+        )
+    }
+}
+
+impl std::error::Error for VerifyMismatch {}
+
+/// Verify `path` (whose ROM entry in the DAT is named `filename`) against the
+/// DAT's recorded size/CRC32/MD5/SHA1, returning the computed `FileHashes` on
+/// success or a `VerifyMismatch` on any discrepancy.
+pub fn verify(
+    dat: &DatFile,
+    filename: &str,
+    path: &Path,
+) -> Result<FileHashes, Box<dyn std::error::Error>> {
+    let Some(expected) = dat.get(filename) else {
+        return Err(format!("{} is not present in the DAT", filename).into());
+    };
+
+    let actual = FileHashes::compute(path, expected)?;
+
+    let md5_matches = match (&expected.md5, &actual.md5) {
+        (Some(expected), Some(actual)) => expected.eq_ignore_ascii_case(actual),
+        _ => true,
+    };
+    let sha1_matches = match (&expected.sha1, &actual.sha1) {
+        (Some(expected), Some(actual)) => expected.eq_ignore_ascii_case(actual),
+        _ => true,
+    };
+
+    let matches = actual.size == expected.size
+        && actual.crc32.eq_ignore_ascii_case(&expected.crc)
+        && md5_matches
+        && sha1_matches;
+
+    if matches {
+        Ok(actual)
+    } else {
+        Err(Box::new(VerifyMismatch {
+            filename: filename.to_string(),
+            expected: expected.clone(),
+            actual,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_DAT: &str = r#"
+        <?xml version="1.0"?>
+        <datafile>
+            <game name="Super Game (USA)">
+                <rom name="Super Game (USA).sfc" size="11" crc="0d4a1185" md5="5eb63bbbe01eeed093cb22bb8f5acdc3" sha1="2aae6c35c94fcfb415dbe95f408b9ce91ee846ed"/>
+            </game>
+        </datafile>
+    "#;
+
+    #[test]
+    fn test_parse_and_lookup() {
+        let dat = DatFile::parse(SAMPLE_DAT).unwrap();
+        assert_eq!(dat.len(), 1);
+        assert!(dat.contains("Super Game (USA).sfc"));
+        assert_eq!(dat.get("Super Game (USA).sfc").unwrap().size, 11);
+    }
+
+    #[test]
+    fn test_contains_ignores_extension() {
+        // The DAT records the decompressed rom name (.sfc), but the Myrient
+        // listing we filter against is almost always still the zipped file.
+        let dat = DatFile::parse(SAMPLE_DAT).unwrap();
+        assert!(dat.contains("Super Game (USA).zip"));
+        assert_eq!(dat.get("Super Game (USA).zip").unwrap().size, 11);
+        assert!(!dat.contains("Other Game (USA).zip"));
+    }
+
+    #[test]
+    fn test_verify_success_and_mismatch() {
+        let dir = std::env::temp_dir().join("myrient-filter-datfile-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Super Game (USA).sfc");
+        let dat = DatFile::parse(SAMPLE_DAT).unwrap();
+
+        std::fs::write(&path, b"hello world").unwrap();
+        assert!(verify(&dat, "Super Game (USA).sfc", &path).is_ok());
+
+        std::fs::write(&path, b"corrupted!!").unwrap();
+        assert!(verify(&dat, "Super Game (USA).sfc", &path).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
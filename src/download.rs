@@ -0,0 +1,315 @@
+use futures_util::StreamExt;
+use reqwest::{Client, StatusCode};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Semaphore;
+
+/// Streams downloads to disk instead of buffering whole archives in memory,
+/// resumes partial transfers via HTTP range requests, and bounds how many
+/// downloads run at once.
+#[derive(Debug, Clone)]
+pub struct Downloader {
+    client: Client,
+    semaphore: Arc<Semaphore>,
+}
+
+impl Downloader {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            client: Client::new(),
+            semaphore: Arc::new(Semaphore::new(max_concurrent.max(1))),
+        }
+    }
+
+    /// Download `url` to `dest`, streaming the body through a `.part` file
+    /// alongside it and resuming from the `.part` file's length if one is
+    /// already present and the server honors `Range`. Returns `None` if the
+    /// server responded with a text body (e.g. an error page) rather than
+    /// file content, and an `Err` for any other non-success status so a
+    /// failed download is never mistaken for a completed one.
+    pub async fn download(
+        &self,
+        url: &str,
+        dest: &Path,
+    ) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+        let _permit = self.semaphore.acquire().await?;
+        let part_path = part_path(dest);
+
+        let resume_from = tokio::fs::metadata(&part_path)
+            .await
+            .map(|meta| meta.len())
+            .unwrap_or(0);
+
+        let mut response = self.send(url, resume_from).await?;
+        let mut resuming = false;
+
+        if resume_from > 0 {
+            match response.status() {
+                StatusCode::PARTIAL_CONTENT => resuming = true,
+                StatusCode::RANGE_NOT_SATISFIABLE => {
+                    // The .part file might already hold everything the server
+                    // has to offer - most likely it was complete when a prior
+                    // run was killed between the final flush and the rename -
+                    // but only if the server's reported total length actually
+                    // matches what we have on disk. Otherwise the upstream
+                    // file changed since we last resumed and the .part file
+                    // is stale; re-fetch it from scratch rather than trust it.
+                    let total_length = total_length_from_content_range(&response);
+                    drop(response);
+                    if total_length == Some(resume_from) {
+                        tokio::fs::rename(&part_path, dest).await?;
+                        return Ok(Some(dest.to_path_buf()));
+                    }
+                    response = self.send(url, 0).await?;
+                }
+                // Server ignored our Range request and sent the full body -
+                // use it as-is rather than discarding it and asking again.
+                _ => {}
+            }
+        }
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("GET {} failed with status {}", url, status).into());
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+
+        if content_type.contains("text") && !resuming {
+            let text = response.text().await?;
+            println!("Received text response:\n{}", text);
+            return Ok(None);
+        }
+
+        let mut file = if resuming {
+            tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(&part_path)
+                .await?
+        } else {
+            tokio::fs::File::create(&part_path).await?
+        };
+
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            file.write_all(&chunk?).await?;
+        }
+        file.flush().await?;
+        drop(file);
+
+        tokio::fs::rename(&part_path, dest).await?;
+        Ok(Some(dest.to_path_buf()))
+    }
+
+    /// Issue the GET request, attaching a `Range` header when resuming from
+    /// a non-zero offset.
+    async fn send(&self, url: &str, resume_from: u64) -> Result<reqwest::Response, reqwest::Error> {
+        let mut request = self.client.get(url);
+        if resume_from > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", resume_from));
+        }
+        request.send().await
+    }
+}
+
+fn part_path(dest: &Path) -> PathBuf {
+    let mut os_str = dest.as_os_str().to_os_string();
+    os_str.push(".part");
+    PathBuf::from(os_str)
+}
+
+/// Parse the resource's total length out of a `Content-Range: bytes */N`
+/// header, as servers send on a 416 response. `None` if absent or the total
+/// is reported as unknown (`*`).
+fn total_length_from_content_range(response: &reqwest::Response) -> Option<u64> {
+    let value = response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?;
+    value.rsplit('/').next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Read a raw HTTP request up to the blank line terminating its headers,
+    /// handing the stream back so the caller can write a response on it.
+    async fn read_request_headers(stream: TcpStream) -> (String, TcpStream) {
+        let mut reader = BufReader::new(stream);
+        let mut headers = String::new();
+        loop {
+            let mut line = String::new();
+            let n = reader.read_line(&mut line).await.unwrap();
+            if n == 0 || line == "\r\n" {
+                break;
+            }
+            headers.push_str(&line);
+        }
+        (headers, reader.into_inner())
+    }
+
+    #[tokio::test]
+    async fn test_resume_sends_range_header_and_appends() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let dir = std::env::temp_dir().join("myrient-filter-download-test-resume");
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("rom.bin");
+        let part = part_path(&dest);
+        std::fs::write(&part, b"hello ").unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (headers, mut stream) = read_request_headers(stream).await;
+            assert!(headers.to_ascii_lowercase().contains("range: bytes=6-"));
+            let body = b"world";
+            let response =
+                format!("HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\n\r\n", body.len());
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.write_all(body).await.unwrap();
+        });
+
+        let downloader = Downloader::new(1);
+        let url = format!("http://{}/rom.bin", addr);
+        let result = downloader.download(&url, &dest).await.unwrap();
+        server.await.unwrap();
+
+        assert_eq!(result, Some(dest.clone()));
+        assert_eq!(std::fs::read(&dest).unwrap(), b"hello world");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_non_206_resume_uses_full_body_already_in_hand() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let dir = std::env::temp_dir().join("myrient-filter-download-test-fallback");
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("rom.bin");
+        let part = part_path(&dest);
+        std::fs::write(&part, b"stale garbage").unwrap();
+
+        let full_body: &[u8] = b"fresh full content";
+        let server = tokio::spawn(async move {
+            // The server doesn't support Range at all and answers the resume
+            // attempt with a plain 200 and the full body - a single request
+            // should be enough; the response shouldn't be thrown away and
+            // asked for again.
+            let (stream, _) = listener.accept().await.unwrap();
+            let (headers, mut stream) = read_request_headers(stream).await;
+            assert!(headers.to_ascii_lowercase().contains("range: bytes=13-"));
+            let response =
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", full_body.len());
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.write_all(full_body).await.unwrap();
+        });
+
+        let downloader = Downloader::new(1);
+        let url = format!("http://{}/rom.bin", addr);
+        let result = downloader.download(&url, &dest).await.unwrap();
+        server.await.unwrap();
+
+        assert_eq!(result, Some(dest.clone()));
+        assert_eq!(std::fs::read(&dest).unwrap(), full_body);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_416_with_matching_length_finalizes_existing_part_file() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let dir = std::env::temp_dir().join("myrient-filter-download-test-416-match");
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("rom.bin");
+        let part = part_path(&dest);
+        std::fs::write(&part, b"already complete").unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (_, mut stream) = read_request_headers(stream).await;
+            // "already complete" is 16 bytes - the server confirms that's
+            // the full length of the resource via Content-Range.
+            stream
+                .write_all(
+                    b"HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */16\r\nContent-Length: 0\r\n\r\n",
+                )
+                .await
+                .unwrap();
+        });
+
+        let downloader = Downloader::new(1);
+        let url = format!("http://{}/rom.bin", addr);
+        let result = downloader.download(&url, &dest).await.unwrap();
+        server.await.unwrap();
+
+        assert_eq!(result, Some(dest.clone()));
+        assert_eq!(std::fs::read(&dest).unwrap(), b"already complete");
+        assert!(!part.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_416_with_mismatched_length_refetches_from_scratch() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let dir = std::env::temp_dir().join("myrient-filter-download-test-416-mismatch");
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("rom.bin");
+        let part = part_path(&dest);
+        // A stale .part file left over from a since-replaced upstream file.
+        std::fs::write(&part, b"stale leftover bytes").unwrap();
+
+        let fresh_body: &[u8] = b"brand new file";
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (_, mut stream) = read_request_headers(stream).await;
+            // The real resource is shorter than our stale .part file, so the
+            // server's reported total length doesn't match what's on disk.
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 416 Range Not Satisfiable\r\nConnection: close\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\n\r\n",
+                        fresh_body.len()
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+            stream.shutdown().await.unwrap();
+
+            let (stream, _) = listener.accept().await.unwrap();
+            let (_, mut stream) = read_request_headers(stream).await;
+            let response =
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", fresh_body.len());
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.write_all(fresh_body).await.unwrap();
+        });
+
+        let downloader = Downloader::new(1);
+        let url = format!("http://{}/rom.bin", addr);
+        let result = downloader.download(&url, &dest).await.unwrap();
+        server.await.unwrap();
+
+        assert_eq!(result, Some(dest.clone()));
+        assert_eq!(std::fs::read(&dest).unwrap(), fresh_body);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Extract `archive` into `dest`, creating `dest` if necessary, and return the
+/// paths of the files that were written. The format is picked from the
+/// archive's extension; unrecognized extensions are left untouched and
+/// returned as-is so callers can still treat them as "the ROM".
+pub fn extract(archive: &Path, dest: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    match archive
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("zip") => extract_zip(archive, dest),
+        #[cfg(feature = "sevenz")]
+        Some("7z") => extract_7z(archive, dest),
+        #[cfg(feature = "zstd")]
+        Some("zst") => extract_zst(archive, dest),
+        // Not a format we know how to unpack - leave it where it is.
+        _ => Ok(vec![archive.to_path_buf()]),
+    }
+}
+
+fn extract_zip(archive: &Path, dest: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(dest)?;
+    let file = File::open(archive)?;
+    let mut zip = zip::ZipArchive::new(file)?;
+
+    let mut extracted = Vec::with_capacity(zip.len());
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let Some(enclosed) = entry.enclosed_name() else {
+            continue;
+        };
+        let out_path = dest.join(enclosed);
+
+        if entry.is_dir() {
+            std::fs::create_dir_all(&out_path)?;
+            continue;
+        }
+
+        if let Some(parent) = out_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut out_file = File::create(&out_path)?;
+        io::copy(&mut entry, &mut out_file)?;
+        extracted.push(out_path);
+    }
+
+    Ok(extracted)
+}
+
+#[cfg(feature = "sevenz")]
+fn extract_7z(archive: &Path, dest: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(dest)?;
+    let mut extracted = Vec::new();
+    sevenz_rust::decompress_file_with_extract_fn(archive, dest, |entry, reader, path| {
+        let written = sevenz_rust::default_entry_extract_fn(entry, reader, path)?;
+        if !entry.is_directory() {
+            extracted.push(path.clone());
+        }
+        Ok(written)
+    })?;
+    Ok(extracted)
+}
+
+#[cfg(feature = "zstd")]
+fn extract_zst(archive: &Path, dest: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(dest)?;
+    let mut input = File::open(archive)?;
+    let out_name = archive
+        .file_stem()
+        .ok_or("archive path has no file stem")?;
+    let out_path = dest.join(out_name);
+    let mut out_file = File::create(&out_path)?;
+    zstd::stream::copy_decode(&mut input, &mut out_file)?;
+    Ok(vec![out_path])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_extract_zip() {
+        let dir = std::env::temp_dir().join("myrient-filter-extract-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive_path = dir.join("test.zip");
+
+        {
+            let file = File::create(&archive_path).unwrap();
+            let mut writer = zip::ZipWriter::new(file);
+            writer
+                .start_file("rom.bin", zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(b"hello world").unwrap();
+            writer.finish().unwrap();
+        }
+
+        let dest = dir.join("out");
+        let extracted = extract(&archive_path, &dest).unwrap();
+
+        assert_eq!(extracted.len(), 1);
+        assert_eq!(std::fs::read(&extracted[0]).unwrap(), b"hello world");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,17 +2,157 @@ use reqwest::Client;
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use url::Url;
+
+pub mod config;
+pub mod datfile;
+pub mod download;
+pub mod extract;
+pub mod sync_state;
+
+pub use config::{Config, Profile};
+pub use datfile::DatFile;
+pub use download::Downloader;
+pub use sync_state::{SyncEntry, SyncState};
+
 pub const NO_INTRO_DIR: &str = "No-Intro";
 pub const BASE_URL: &str = "https://myrient.erista.me/files/";
 
+/// Extract every term found in parentheses, e.g. `"Game (USA) (Rev 1).zip"` -> `["USA", "Rev 1"]`.
+fn get_terms_in_parentheses(filename: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut current_term = String::new();
+    let mut in_parentheses = false;
+
+    for c in filename.chars() {
+        match c {
+            '(' => {
+                in_parentheses = true;
+                current_term.clear();
+            }
+            ')' if in_parentheses => {
+                terms.push(current_term.clone());
+                in_parentheses = false;
+            }
+            _ if in_parentheses => {
+                current_term.push(c);
+            }
+            _ => {}
+        }
+    }
+    terms
+}
+
+/// A disc/side/tape index parsed from one of a ROM filename's parenthesized
+/// terms, e.g. `(Disc 2)` or `(Side B)`. Games sharing a base name but
+/// carrying different `DiscTag`s are a multi-disc set, not revisions of one
+/// another, and must be kept together rather than deduplicated.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct DiscTag {
+    kind: String,
+    index: u32,
+}
+
+fn parse_disc_tag(filename: &str) -> Option<DiscTag> {
+    let re = regex::Regex::new(r"(?i)^(Disc|Disk|Tape)\s*(\d+)$|^Side\s*([A-Za-z])$").unwrap();
+
+    for term in get_terms_in_parentheses(filename) {
+        let Some(caps) = re.captures(term.trim()) else {
+            continue;
+        };
+
+        if let (Some(kind), Some(num)) = (caps.get(1), caps.get(2)) {
+            if let Ok(index) = num.as_str().parse() {
+                return Some(DiscTag {
+                    kind: kind.as_str().to_string(),
+                    index,
+                });
+            }
+        } else if let Some(letter) = caps.get(3) {
+            let letter = letter.as_str().to_ascii_uppercase().chars().next().unwrap();
+            return Some(DiscTag {
+                kind: "Side".to_string(),
+                index: letter as u32 - 'A' as u32 + 1,
+            });
+        }
+    }
+
+    None
+}
+
+/// Group `roms` (the output of `RomLister::list_roms`) into multi-disc sets,
+/// keyed by base name, keeping only sets with more than one member and
+/// sorting each set by disc/side/tape index.
+pub fn group_discs(roms: &[Rom]) -> BTreeMap<String, Vec<Rom>> {
+    let mut sets: BTreeMap<String, Vec<(DiscTag, Rom)>> = BTreeMap::new();
+
+    for rom in roms {
+        let Some(disc) = parse_disc_tag(&rom.filename) else {
+            continue;
+        };
+        let (base_name, _revision) = RomLister::get_base_name_and_revision(&rom.filename);
+        sets.entry(base_name).or_default().push((disc, rom.clone()));
+    }
+
+    sets.into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(base_name, mut members)| {
+            members.sort_by(|a, b| a.0.cmp(&b.0));
+            (base_name, members.into_iter().map(|(_, rom)| rom).collect())
+        })
+        .collect()
+}
+
+/// Write an `.m3u` playlist for a multi-disc set to `dir/{game_name}.m3u`,
+/// listing each member's filename on its own line in disc order.
+pub fn write_playlist(
+    dir: &Path,
+    game_name: &str,
+    members: &[Rom],
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = dir.join(format!("{}.m3u", game_name));
+    let contents = members
+        .iter()
+        .map(|rom| rom.filename.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    std::fs::write(&path, contents)?;
+    Ok(path)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FilterOptions {
     pub region_limit: bool,
-    pub region: String,
+    /// Regions in descending order of preference, e.g. `["USA", "World", "Europe", "Japan"]`.
+    /// When `latest_revision` is set, the base-name grouping picks the single
+    /// release whose region is earliest in this list, breaking ties by
+    /// revision - i.e. a One-Game-One-Region selection.
+    pub region_priority: Vec<String>,
     pub smart_filters: bool,
     pub exclude_patterns: Vec<String>,
     pub latest_revision: bool,
+    /// Leave downloaded archives intact instead of extracting them, for
+    /// systems whose cores load compressed content directly or whose ROMs
+    /// are multi-file sets that shouldn't be flattened.
+    #[serde(default)]
+    pub block_extract: bool,
+    /// A No-Intro Logiqx DAT to verify downloads against. When set, only ROMs
+    /// listed in it pass filtering; the filename heuristics below still apply
+    /// on top of it.
+    #[serde(default)]
+    pub dat_path: Option<PathBuf>,
+    /// How many downloads to run at once.
+    #[serde(default = "default_max_concurrent_downloads")]
+    pub max_concurrent_downloads: usize,
+    /// Emit an `.m3u` playlist for each multi-disc/side/tape set so
+    /// emulators can load the whole set as one title.
+    #[serde(default)]
+    pub generate_playlists: bool,
+}
+
+fn default_max_concurrent_downloads() -> usize {
+    4
 }
 
 #[derive(Debug, Clone)]
@@ -35,6 +175,23 @@ impl RomLister {
         }
     }
 
+    /// Build a `RomLister` from a named profile in a config file, following the
+    /// same `[profiles.<name>]` layout as `Config`. Returns the lister along with
+    /// the resolved `Profile` so the caller can learn the target system, subdir,
+    /// and download directory without re-reading the config.
+    pub fn from_profile(
+        path: &Path,
+        name: Option<&str>,
+    ) -> Result<(Self, Profile), Box<dyn std::error::Error>> {
+        let config = Config::load(path)?;
+        let profile = config
+            .profile(name)
+            .ok_or_else(|| format!("no such profile: {:?}", name))?
+            .clone();
+
+        Ok((Self::new(profile.filter.clone()), profile))
+    }
+
     /// List directories at the given path. If no path is provided, lists directories at the base URL
     pub async fn list_directories(
         &self,
@@ -82,10 +239,15 @@ impl RomLister {
         let document = Html::parse_document(&response);
         let selector = Selector::parse("tbody > tr > td.link > a").unwrap();
 
+        let dat = match &self.options.dat_path {
+            Some(path) => Some(DatFile::load(path)?),
+            None => None,
+        };
+
         let urls: Vec<String> = document
             .select(&selector)
             .skip(1)
-            .filter(|link| self.is_valid_file(link.value().attr("href").unwrap_or("")))
+            .filter(|link| self.is_valid_file(link.value().attr("href").unwrap_or(""), dat.as_ref()))
             .map(|link| {
                 let href = link.value().attr("href").unwrap();
                 if !href.starts_with("http") {
@@ -122,8 +284,10 @@ impl RomLister {
                 .collect());
         }
 
-        // Group ROMs by base name using BTreeMap for automatic sorting
-        let mut rom_groups: BTreeMap<String, Vec<Rom>> = BTreeMap::new();
+        // Group ROMs by base name (and disc/side/tape index, so multi-disc
+        // sets don't get collapsed into a single file) using BTreeMap for
+        // automatic sorting
+        let mut rom_groups: BTreeMap<(String, Option<DiscTag>), Vec<Rom>> = BTreeMap::new();
 
         for url in urls {
             let url_obj = Url::parse(&url).unwrap();
@@ -133,22 +297,24 @@ impl RomLister {
             let filename = path.file_name().unwrap().to_string_lossy().to_string();
 
             let (base_name, _revision) = Self::get_base_name_and_revision(&filename);
+            let disc = parse_disc_tag(&filename);
             let rom = Rom { filename, url };
 
-            rom_groups.entry(base_name).or_default().push(rom);
+            rom_groups.entry((base_name, disc)).or_default().push(rom);
         }
 
-        // For each group, keep only the latest revision
+        // For each group, keep the single release whose region is earliest in
+        // `region_priority`, breaking ties by latest revision (One-Game-One-Region)
         let mut final_roms = Vec::new();
         for roms in rom_groups.values() {
             if roms.len() == 1 {
                 final_roms.push(roms[0].clone());
             } else {
-                let latest = roms.iter().max_by_key(|rom| {
+                let best = roms.iter().min_by_key(|rom| {
                     let (_, revision) = Self::get_base_name_and_revision(&rom.filename);
-                    revision.unwrap_or(-1)
+                    (self.region_rank(&rom.filename), std::cmp::Reverse(revision.unwrap_or(-1)))
                 });
-                if let Some(rom) = latest {
+                if let Some(rom) = best {
                     final_roms.push(rom.clone());
                 }
             }
@@ -157,47 +323,30 @@ impl RomLister {
         Ok(final_roms)
     }
 
-    fn is_valid_file(&self, href: &str) -> bool {
-        let file_name = urlencoding::decode(href.split('/').last().unwrap_or(""))
+    fn is_valid_file(&self, href: &str, dat: Option<&DatFile>) -> bool {
+        let file_name = urlencoding::decode(href.split('/').next_back().unwrap_or(""))
             .unwrap_or_default()
             .into_owned();
 
-        // Helper function to extract terms in parentheses
-        fn get_terms_in_parentheses(filename: &str) -> Vec<String> {
-            let mut terms = Vec::new();
-            let mut current_term = String::new();
-            let mut in_parentheses = false;
-
-            for c in filename.chars() {
-                match c {
-                    '(' => {
-                        in_parentheses = true;
-                        current_term.clear();
-                    }
-                    ')' => {
-                        if in_parentheses {
-                            terms.push(current_term.clone());
-                            in_parentheses = false;
-                        }
-                    }
-                    _ if in_parentheses => {
-                        current_term.push(c);
-                    }
-                    _ => {}
-                }
+        // When a DAT is supplied, it gates membership - a ROM not listed in
+        // the DAT is rejected outright - but the region/exclude/smart-filter
+        // checks below still apply on top of it.
+        if let Some(dat) = dat {
+            if !dat.contains(&file_name) {
+                return false;
             }
-            terms
         }
 
         // Get all terms in parentheses
         let terms = get_terms_in_parentheses(&file_name);
 
         // Check region first
-        if self.options.region_limit {
-            let regions = [&self.options.region, "World"];
-            if !terms.iter().any(|term| regions.contains(&term.as_str())) {
-                return false;
-            }
+        if self.options.region_limit
+            && !terms
+                .iter()
+                .any(|term| self.options.region_priority.iter().any(|r| r == term))
+        {
+            return false;
         }
 
         // Check excluded patterns
@@ -238,6 +387,17 @@ impl RomLister {
         true
     }
 
+    /// Index of the best (lowest) `region_priority` entry present in
+    /// `filename`'s parenthesized terms, or `usize::MAX` if none match.
+    fn region_rank(&self, filename: &str) -> usize {
+        let terms = get_terms_in_parentheses(filename);
+        self.options
+            .region_priority
+            .iter()
+            .position(|region| terms.iter().any(|term| term == region))
+            .unwrap_or(usize::MAX)
+    }
+
     fn get_base_name_and_revision(filename: &str) -> (String, Option<i32>) {
         // Match everything up to the last sequence of metadata parentheses
         // Uses negative lookahead to ensure we don't stop at parentheses that are part of the name
@@ -275,20 +435,24 @@ mod tests {
     fn test_is_valid_file() {
         let options = FilterOptions {
             region_limit: true,
-            region: "Europe".to_string(),
+            region_priority: vec!["Europe".to_string(), "World".to_string()],
             smart_filters: true,
             exclude_patterns: vec!["Beta".to_string(), "Rev B".to_string()],
             latest_revision: true,
+            block_extract: false,
+            dat_path: None,
+            max_concurrent_downloads: 4,
+            generate_playlists: false,
         };
 
         let rom_lister = RomLister::new(options);
 
-        assert!(rom_lister.is_valid_file("Super Game (Europe).zip"));
-        assert!(rom_lister.is_valid_file("Super Game (World).zip"));
-        assert!(!rom_lister.is_valid_file("Super Game (USA).zip"));
-        assert!(!rom_lister.is_valid_file("Super Game (Beta).zip"));
-        assert!(!rom_lister.is_valid_file("Super Game (Rev B).zip"));
-        assert!(rom_lister.is_valid_file("Beta Game (Europe).zip")); // Should pass as Beta is not in parentheses
+        assert!(rom_lister.is_valid_file("Super Game (Europe).zip", None));
+        assert!(rom_lister.is_valid_file("Super Game (World).zip", None));
+        assert!(!rom_lister.is_valid_file("Super Game (USA).zip", None));
+        assert!(!rom_lister.is_valid_file("Super Game (Beta).zip", None));
+        assert!(!rom_lister.is_valid_file("Super Game (Rev B).zip", None));
+        assert!(rom_lister.is_valid_file("Beta Game (Europe).zip", None)); // Should pass as Beta is not in parentheses
     }
 
     #[test]
@@ -348,41 +512,144 @@ mod tests {
     fn test_is_valid_file_exclusions() {
         let options = FilterOptions {
             region_limit: true,
-            region: "USA".to_string(),
+            region_priority: vec!["USA".to_string(), "World".to_string()],
             smart_filters: true,
             exclude_patterns: vec!["Rental".to_string(), "Alt".to_string()],
             latest_revision: true,
+            block_extract: false,
+            dat_path: None,
+            max_concurrent_downloads: 4,
+            generate_playlists: false,
         };
 
         let rom_lister = RomLister::new(options);
 
         // Region filtering
-        assert!(rom_lister.is_valid_file("Game (USA).zip"));
-        assert!(rom_lister.is_valid_file("Game (World).zip"));
-        assert!(!rom_lister.is_valid_file("Game (Europe).zip"));
-        assert!(!rom_lister.is_valid_file("Game (Japan).zip"));
+        assert!(rom_lister.is_valid_file("Game (USA).zip", None));
+        assert!(rom_lister.is_valid_file("Game (World).zip", None));
+        assert!(!rom_lister.is_valid_file("Game (Europe).zip", None));
+        assert!(!rom_lister.is_valid_file("Game (Japan).zip", None));
 
         // Smart filters
-        assert!(!rom_lister.is_valid_file("Game (USA) (Beta).zip"));
-        assert!(!rom_lister.is_valid_file("Game (USA) (Proto).zip"));
-        assert!(!rom_lister.is_valid_file("Game (USA) (Sample).zip"));
-        assert!(!rom_lister.is_valid_file("Game (USA) (Demo).zip"));
-        assert!(!rom_lister.is_valid_file("Game (USA) (Kiosk).zip"));
-        assert!(!rom_lister.is_valid_file("Game (USA) (Unl).zip"));
+        assert!(!rom_lister.is_valid_file("Game (USA) (Beta).zip", None));
+        assert!(!rom_lister.is_valid_file("Game (USA) (Proto).zip", None));
+        assert!(!rom_lister.is_valid_file("Game (USA) (Sample).zip", None));
+        assert!(!rom_lister.is_valid_file("Game (USA) (Demo).zip", None));
+        assert!(!rom_lister.is_valid_file("Game (USA) (Kiosk).zip", None));
+        assert!(!rom_lister.is_valid_file("Game (USA) (Unl).zip", None));
 
         // Custom exclude patterns
-        assert!(!rom_lister.is_valid_file("Game (USA) (Rental Version).zip"));
-        assert!(!rom_lister.is_valid_file("Game (USA) (Alt Version).zip"));
+        assert!(!rom_lister.is_valid_file("Game (USA) (Rental Version).zip", None));
+        assert!(!rom_lister.is_valid_file("Game (USA) (Alt Version).zip", None));
 
         // Complex combinations
-        assert!(!rom_lister.is_valid_file("Game (Beta) (USA) (Rev 1).zip")); // Smart filter should catch this
-        assert!(!rom_lister.is_valid_file("Game (Rental) (World) (Rev 2).zip")); // Custom pattern should catch this
-        assert!(!rom_lister.is_valid_file("Game (Europe) (Rev 1) (Demo).zip")); // Region and smart filter both invalid
+        assert!(!rom_lister.is_valid_file("Game (Beta) (USA) (Rev 1).zip", None)); // Smart filter should catch this
+        assert!(!rom_lister.is_valid_file("Game (Rental) (World) (Rev 2).zip", None)); // Custom pattern should catch this
+        assert!(!rom_lister.is_valid_file("Game (Europe) (Rev 1) (Demo).zip", None)); // Region and smart filter both invalid
 
         // These should pass
-        assert!(rom_lister.is_valid_file("Game (Rev 2) (USA).zip"));
-        assert!(rom_lister.is_valid_file("Game with Beta in Title (USA).zip")); // Beta not in parentheses
-        assert!(rom_lister.is_valid_file("Alternative Game (USA).zip")); // Alt not in parentheses
-        assert!(rom_lister.is_valid_file("Game (World) (Rev 1).zip"));
+        assert!(rom_lister.is_valid_file("Game (Rev 2) (USA).zip", None));
+        assert!(rom_lister.is_valid_file("Game with Beta in Title (USA).zip", None)); // Beta not in parentheses
+        assert!(rom_lister.is_valid_file("Alternative Game (USA).zip", None)); // Alt not in parentheses
+        assert!(rom_lister.is_valid_file("Game (World) (Rev 1).zip", None));
+    }
+
+    #[test]
+    fn test_region_rank_priority_order() {
+        let options = FilterOptions {
+            region_limit: false,
+            region_priority: vec!["USA".to_string(), "World".to_string(), "Europe".to_string()],
+            smart_filters: false,
+            exclude_patterns: vec![],
+            latest_revision: true,
+            block_extract: false,
+            dat_path: None,
+            max_concurrent_downloads: 4,
+            generate_playlists: false,
+        };
+        let rom_lister = RomLister::new(options);
+
+        assert_eq!(rom_lister.region_rank("Game (USA).zip"), 0);
+        assert_eq!(rom_lister.region_rank("Game (World).zip"), 1);
+        assert_eq!(rom_lister.region_rank("Game (Europe).zip"), 2);
+        assert_eq!(rom_lister.region_rank("Game (Japan).zip"), usize::MAX);
+    }
+
+    #[test]
+    fn test_is_valid_file_dat_and_region_limit() {
+        const DAT_XML: &str = r#"
+            <?xml version="1.0"?>
+            <datafile>
+                <game name="Super Game">
+                    <rom name="Super Game (USA).sfc" size="11" crc="0d4a1185"/>
+                    <rom name="Super Game (Europe).sfc" size="11" crc="0d4a1185"/>
+                </game>
+            </datafile>
+        "#;
+        let dat = DatFile::parse(DAT_XML).unwrap();
+
+        let options = FilterOptions {
+            region_limit: true,
+            region_priority: vec!["USA".to_string()],
+            smart_filters: false,
+            exclude_patterns: vec![],
+            latest_revision: true,
+            block_extract: false,
+            dat_path: None,
+            max_concurrent_downloads: 4,
+            generate_playlists: false,
+        };
+        let rom_lister = RomLister::new(options);
+
+        // In the DAT and USA - passes both the DAT gate and region_limit.
+        assert!(rom_lister.is_valid_file("Super Game (USA).zip", Some(&dat)));
+        // In the DAT but not a priority region - region_limit must still reject it.
+        assert!(!rom_lister.is_valid_file("Super Game (Europe).zip", Some(&dat)));
+        // Not in the DAT at all - rejected regardless of region.
+        assert!(!rom_lister.is_valid_file("Other Game (USA).zip", Some(&dat)));
+    }
+
+    #[test]
+    fn test_parse_disc_tag() {
+        assert_eq!(
+            parse_disc_tag("Game (Disc 1) (USA).zip"),
+            Some(DiscTag {
+                kind: "Disc".to_string(),
+                index: 1
+            })
+        );
+        assert_eq!(
+            parse_disc_tag("Game (USA) (Side B).zip"),
+            Some(DiscTag {
+                kind: "Side".to_string(),
+                index: 2
+            })
+        );
+        assert_eq!(parse_disc_tag("Game (USA).zip"), None);
+    }
+
+    #[test]
+    fn test_group_discs() {
+        let roms = vec![
+            Rom {
+                filename: "Game (Disc 1) (USA).zip".to_string(),
+                url: "https://example.com/Game (Disc 1) (USA).zip".to_string(),
+            },
+            Rom {
+                filename: "Game (Disc 2) (USA).zip".to_string(),
+                url: "https://example.com/Game (Disc 2) (USA).zip".to_string(),
+            },
+            Rom {
+                filename: "Other Game (USA).zip".to_string(),
+                url: "https://example.com/Other Game (USA).zip".to_string(),
+            },
+        ];
+
+        let sets = group_discs(&roms);
+        assert_eq!(sets.len(), 1);
+        let discs = &sets["Game"];
+        assert_eq!(discs.len(), 2);
+        assert_eq!(discs[0].filename, "Game (Disc 1) (USA).zip");
+        assert_eq!(discs[1].filename, "Game (Disc 2) (USA).zip");
     }
 }
@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the state file written to each download directory.
+pub const SYNC_STATE_FILENAME: &str = ".myrient-sync.json";
+
+/// What we know about a previously-synced ROM, keyed by filename in
+/// `SyncState::entries`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncEntry {
+    pub source_url: String,
+    /// Paths written for this ROM, already joined onto the download
+    /// directory - usually one file, but extraction can produce several.
+    pub files: Vec<PathBuf>,
+    pub size: u64,
+    pub verified_hash: Option<String>,
+}
+
+/// Persisted record of what's already been downloaded into a directory, so a
+/// subsequent run can skip unchanged ROMs and optionally prune stale ones.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SyncState {
+    pub entries: HashMap<String, SyncEntry>,
+}
+
+impl SyncState {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Path to the state file for a given download directory.
+    pub fn path_for(download_dir: &Path) -> PathBuf {
+        download_dir.join(SYNC_STATE_FILENAME)
+    }
+
+    pub fn record(&mut self, filename: String, entry: SyncEntry) {
+        self.entries.insert(filename, entry);
+    }
+}
+
+/// The result of diffing a freshly-filtered ROM list against a `SyncState`:
+/// what's new or changed and needs downloading, and - when pruning - what's
+/// in the state but no longer matched by the current filter.
+#[derive(Debug, Clone, Default)]
+pub struct SyncPlan<'a> {
+    pub to_download: Vec<&'a crate::Rom>,
+    pub to_prune: Vec<String>,
+}
+
+/// Diff `roms` (the output of `RomLister::list_roms`) against `state`,
+/// treating a ROM as unchanged only if its filename is already recorded with
+/// the same source URL. When `prune` is set, `to_prune` lists every state
+/// entry whose filename is no longer present in `roms`.
+pub fn plan_sync<'a>(roms: &'a [crate::Rom], state: &SyncState, prune: bool) -> SyncPlan<'a> {
+    let mut to_download = Vec::new();
+    for rom in roms {
+        match state.entries.get(&rom.filename) {
+            Some(entry) if entry.source_url == rom.url => {}
+            _ => to_download.push(rom),
+        }
+    }
+
+    let to_prune = if prune {
+        let current: std::collections::HashSet<&str> =
+            roms.iter().map(|rom| rom.filename.as_str()).collect();
+        state
+            .entries
+            .keys()
+            .filter(|filename| !current.contains(filename.as_str()))
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    SyncPlan {
+        to_download,
+        to_prune,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rom;
+
+    fn rom(filename: &str, url: &str) -> Rom {
+        Rom {
+            filename: filename.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_plan_sync_skips_unchanged() {
+        let mut state = SyncState::default();
+        state.record(
+            "Game (USA).zip".to_string(),
+            SyncEntry {
+                source_url: "https://example.com/Game (USA).zip".to_string(),
+                files: vec![PathBuf::from("Game (USA).zip")],
+                size: 123,
+                verified_hash: None,
+            },
+        );
+
+        let roms = vec![
+            rom("Game (USA).zip", "https://example.com/Game (USA).zip"),
+            rom("New Game (USA).zip", "https://example.com/New Game (USA).zip"),
+        ];
+
+        let plan = plan_sync(&roms, &state, false);
+        assert_eq!(plan.to_download.len(), 1);
+        assert_eq!(plan.to_download[0].filename, "New Game (USA).zip");
+        assert!(plan.to_prune.is_empty());
+    }
+
+    #[test]
+    fn test_plan_sync_prune() {
+        let mut state = SyncState::default();
+        state.record(
+            "Removed Game (USA).zip".to_string(),
+            SyncEntry {
+                source_url: "https://example.com/Removed Game (USA).zip".to_string(),
+                files: vec![PathBuf::from("Removed Game (USA).zip")],
+                size: 42,
+                verified_hash: None,
+            },
+        );
+
+        let roms = vec![rom(
+            "New Game (USA).zip",
+            "https://example.com/New Game (USA).zip",
+        )];
+
+        let plan = plan_sync(&roms, &state, true);
+        assert_eq!(plan.to_prune, vec!["Removed Game (USA).zip".to_string()]);
+    }
+}